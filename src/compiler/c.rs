@@ -0,0 +1,94 @@
+// Copyright 2016 Mozilla Foundation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// The language of a compiler input, as determined from its extension or an
+/// explicit `-x`-style override.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    C,
+    Cxx,
+    ObjectiveC,
+    ObjectiveCxx,
+    /// Raw assembly (`.s`), not run through the preprocessor.
+    Assembly,
+    /// Preprocessed assembly (`.S`).
+    AssemblyWithCpp,
+    /// A C header being compiled to a precompiled header (`-x c-header`).
+    CHeader,
+    /// A C++ header being compiled to a precompiled header (`-x c++-header`).
+    CxxHeader,
+}
+
+/// Which C/C++ compiler frontend produced a given command line, so we know
+/// which argument dialect to parse it with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CCompilerKind {
+    Gcc,
+    Clang,
+    /// clang running in its MSVC-compatible `clang-cl` driver mode.
+    ClangCl,
+    Msvc,
+}
+
+impl CCompilerKind {
+    /// `clang-cl` is the same binary as `clang`, invoked under a different
+    /// name (or via `--driver-mode=cl`); tell the two apart from the
+    /// executable name and its own `--version` self-identification, so
+    /// callers can pick the right `CCompilerImpl`. A `--driver-mode=cl`
+    /// argument is not examined here.
+    pub fn from_clang_invocation(executable: &std::path::Path, version_output: &str) -> CCompilerKind {
+        let invoked_as_cl = executable
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .map_or(false, |s| s.eq_ignore_ascii_case("clang-cl"));
+        if invoked_as_cl || version_output.contains("clang-cl") {
+            CCompilerKind::ClangCl
+        } else {
+            CCompilerKind::Clang
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_from_clang_invocation_by_name() {
+        assert_eq!(
+            CCompilerKind::ClangCl,
+            CCompilerKind::from_clang_invocation(Path::new("clang-cl.exe"), "clang version 10.0.0")
+        );
+    }
+
+    #[test]
+    fn test_from_clang_invocation_by_version() {
+        assert_eq!(
+            CCompilerKind::ClangCl,
+            CCompilerKind::from_clang_invocation(
+                Path::new("clang.exe"),
+                "clang-cl version 10.0.0"
+            )
+        );
+    }
+
+    #[test]
+    fn test_from_clang_invocation_default() {
+        assert_eq!(
+            CCompilerKind::Clang,
+            CCompilerKind::from_clang_invocation(Path::new("clang"), "clang version 10.0.0")
+        );
+    }
+}
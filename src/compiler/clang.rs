@@ -44,7 +44,35 @@ impl CCompilerImpl for Clang {
         arguments: &[OsString],
         cwd: &Path,
     ) -> CompilerArguments<ParsedArguments> {
-        gcc::parse_arguments(arguments, cwd, (&gcc::ARGS[..], &ARGS[..]))
+        // Clang's implicit module cache (`-fmodules-cache-path`) writes shared,
+        // content-addressed state outside of our view, so those builds can't
+        // be cached; builds that supply a prebuilt module via `-fmodule-file`
+        // can (a `-fmodule-map-file` alone still uses the implicit cache).
+        if uses_implicit_module_cache(arguments) {
+            return CompilerArguments::CannotCache("implicit clang module cache", None);
+        }
+
+        let mut parsed_args = match gcc::parse_arguments(arguments, cwd, (&gcc::ARGS[..], &ARGS[..])) {
+            CompilerArguments::Ok(parsed_args) => parsed_args,
+            o => return o,
+        };
+        // `-x` takes precedence over the input's extension, same as clang itself.
+        if let Some(lang) = language_from_x_flag(arguments).or_else(|| language_from_file_name(&parsed_args.input)) {
+            parsed_args.language = lang;
+        }
+        // `-include-pch` is captured as a preprocessor argument above, but the
+        // PCH's *contents* also need to be part of the cache key.
+        if let Some(pch) = path_from_flag(arguments, "-include-pch") {
+            parsed_args.extra_hash_files.push(pch.into_os_string());
+        }
+        // Likewise for explicit module builds: hash the module map(s) and any
+        // prebuilt module files so a changed module doesn't produce a stale hit.
+        for flag in &["-fmodule-file", "-fmodule-map-file"] {
+            parsed_args
+                .extra_hash_files
+                .extend(concatenated_values(arguments, flag).map(OsString::from));
+        }
+        CompilerArguments::Ok(parsed_args)
     }
 
     fn preprocess<T>(
@@ -59,6 +87,18 @@ impl CCompilerImpl for Clang {
     where
         T: CommandCreatorSync,
     {
+        if parsed_args.language == Language::Assembly {
+            // Plain assembly isn't run through the preprocessor, so hash the
+            // raw source directly rather than shelling out to clang.
+            let path = cwd.join(&parsed_args.input);
+            return Box::new(future::result(std::fs::read(path).map(
+                |stdout| process::Output {
+                    status: process::ExitStatus::default(),
+                    stdout,
+                    stderr: Vec::new(),
+                },
+            ).map_err(|e| e.into())));
+        }
         gcc::preprocess(creator, executable, parsed_args, cwd, env_vars, may_dist)
     }
 
@@ -70,30 +110,167 @@ impl CCompilerImpl for Clang {
         cwd: &Path,
         env_vars: &[(OsString, OsString)],
     ) -> Result<(CompileCommand, Option<dist::CompileCommand>, Cacheable)> {
-        gcc::generate_compile_commands(path_transformer, executable, parsed_args, cwd, env_vars)
+        let (command, mut dist_command, cacheable) =
+            gcc::generate_compile_commands(path_transformer, executable, parsed_args, cwd, env_vars)?;
+        // A cross-compile points clang at a sysroot/gcc-toolchain that lives
+        // on the local machine, not on the remote build sandbox; the sandbox
+        // only has the toolchain archive, so those trees need to be bundled
+        // into it and the command rewritten to look for them there, or the
+        // dist build can't find them and we silently fall back to local.
+        if let Some(ref mut dist_command) = dist_command {
+            for flag in &["--sysroot", "-isysroot", "-gcc-toolchain"] {
+                if let Some(path) = path_from_flag(&parsed_args.common_args, flag) {
+                    if let Some(dist_path) = path_transformer.request_dir_tree(&cwd.join(&path)) {
+                        rewrite_path_arg(&mut dist_command.arguments, flag, &dist_path);
+                    }
+                }
+            }
+        }
+        Ok((command, dist_command, cacheable))
     }
 }
 
+/// Replace the value following (or concatenated to) `flag` in a rendered
+/// argument list with `new_value`, e.g. to point a remote command at the
+/// in-sandbox location of a tree bundled via `PathTransformer::request_dir_tree`.
+fn rewrite_path_arg(arguments: &mut [String], flag: &str, new_value: &str) {
+    let mut replace_next = false;
+    for arg in arguments.iter_mut() {
+        if replace_next {
+            *arg = new_value.to_owned();
+            replace_next = false;
+        } else if arg == flag {
+            replace_next = true;
+        } else if let Some(rest) = arg.strip_prefix(flag) {
+            if let Some(value) = rest.strip_prefix('=') {
+                if !value.is_empty() {
+                    *arg = format!("{}={}", flag, new_value);
+                }
+            }
+        }
+    }
+}
+
+/// Map a clang `.s`/`.S` input extension to the language it implies.
+fn language_from_file_name(path: &Path) -> Option<Language> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("s") => Some(Language::Assembly),
+        Some("S") => Some(Language::AssemblyWithCpp),
+        _ => None,
+    }
+}
+
+/// Map a `-x <lang>` pair to the language it selects: the assembler forms
+/// from chunk0-1, plus `c-header`/`c++-header`, clang's spelling for "compile
+/// this header to a precompiled header" rather than to an object file.
+fn language_from_x_flag(arguments: &[OsString]) -> Option<Language> {
+    arguments
+        .windows(2)
+        .find(|w| w[0] == "-x")
+        .and_then(|w| w[1].to_str())
+        .and_then(|lang| match lang {
+            "assembler" => Some(Language::Assembly),
+            "assembler-with-cpp" => Some(Language::AssemblyWithCpp),
+            "c-header" => Some(Language::CHeader),
+            "c++-header" => Some(Language::CxxHeader),
+            _ => None,
+        })
+}
+
+/// Find the path given to `flag`, whether it was spelled `<flag> <path>` or
+/// `<flag>=<path>`.
+fn path_from_flag(arguments: &[OsString], flag: &str) -> Option<PathBuf> {
+    arguments
+        .windows(2)
+        .find(|w| w[0] == flag)
+        .map(|w| PathBuf::from(&w[1]))
+        .or_else(|| {
+            arguments.iter().find_map(|arg| {
+                arg.to_str()
+                    .and_then(|s| s.strip_prefix(flag))
+                    .and_then(|s| s.strip_prefix('='))
+                    .map(PathBuf::from)
+            })
+        })
+}
+
+/// Collect the path values of every `<flag>=...` occurrence, taking only the
+/// text after the final `=` so `-fmodule-file=Name=path` resolves to `path`.
+fn concatenated_values<'a>(
+    arguments: &'a [OsString],
+    flag: &str,
+) -> impl Iterator<Item = &'a str> + 'a {
+    let flag = flag.to_owned();
+    arguments.iter().filter_map(move |arg| {
+        arg.to_str()
+            .and_then(|s| s.strip_prefix(&flag))
+            .and_then(|s| s.strip_prefix('='))
+            .map(|s| s.rsplit('=').next().unwrap_or(s))
+    })
+}
+
+/// When `-fmodules`/`-fcxx-modules` is given without any explicit
+/// `-fmodule-file`, clang falls back to its own implicit, shared on-disk
+/// module cache (by default, or whatever `-fmodules-cache-path` points at) —
+/// state we can't see into, so those builds can't be cached here. Only
+/// builds that supply a prebuilt module explicitly are safe to cache.
+///
+/// `-fmodule-map-file` alone doesn't count: it tells clang where a module's
+/// *map* lives, not where its compiled `.pcm` is, so clang will still build
+/// (and implicitly cache) the module itself unless `-fmodule-file` also
+/// hands it the prebuilt artifact.
+fn uses_implicit_module_cache(arguments: &[OsString]) -> bool {
+    let has_modules = arguments
+        .iter()
+        .any(|a| a == "-fmodules" || a == "-fcxx-modules");
+    if !has_modules {
+        return false;
+    }
+    let has_prebuilt_module = arguments
+        .iter()
+        .any(|a| a.to_str().map_or(false, |s| s.starts_with("-fmodule-file")));
+    !has_prebuilt_module
+}
+
 counted_array!(pub static ARGS: [ArgInfo<gcc::ArgData>; _] = [
     take_arg!("--serialize-diagnostics", OsString, Separated, PassThrough),
+    take_arg!("--sysroot", PathBuf, Separated, PassThrough),
     take_arg!("--target", OsString, Separated, PassThrough),
     take_arg!("-Xclang", OsString, Separated, XClang),
     take_arg!("-add-plugin", OsString, Separated, PassThrough),
+    // Explicit modules (module map(s)/prebuilt modules hashed below) are
+    // cacheable; only the *implicit* module cache is rejected, in
+    // `uses_implicit_module_cache`.
+    flag!("-fbuiltin-module-map", PassThrough),
     flag!("-fcolor-diagnostics", DiagnosticsColorFlag),
-    flag!("-fcxx-modules", TooHardFlag),
+    flag!("-fcxx-modules", PassThrough),
     take_arg!("-fdebug-compilation-dir", OsString, Separated, PassThrough),
-    flag!("-fmodules", TooHardFlag),
+    take_arg!("-fmodule-file", PathBuf, CanBeConcatenated('='), PassThrough),
+    take_arg!("-fmodule-map-file", PathBuf, CanBeConcatenated('='), PassThrough),
+    flag!("-fmodules", PassThrough),
+    take_arg!("-fmodules-cache-path", PathBuf, CanBeConcatenated('='), PassThrough),
     flag!("-fno-color-diagnostics", NoDiagnosticsColorFlag),
     take_arg!("-fplugin", PathBuf, CanBeConcatenated('='), ExtraHashFile),
     flag!("-fprofile-instr-generate", ProfileGenerate),
-    // Can be either -fprofile-instr-use or -fprofile-instr-use=path
-    take_arg!("-fprofile-instr-use", OsString, Concatenated, TooHard),
-    take_arg!("-gcc-toolchain", OsString, Separated, PassThrough),
+    // Can be either -fprofile-instr-use path or -fprofile-instr-use=path; the
+    // referenced .profdata is folded into the cache key like any other
+    // auxiliary input file. Spelled out explicitly (rather than the bare
+    // `Concatenated`) so the captured value is the path itself, not `=path`.
+    take_arg!("-fprofile-instr-use", PathBuf, CanBeConcatenated('='), ExtraHashFile),
+    take_arg!("-fprofile-sample-use", PathBuf, CanBeConcatenated('='), ExtraHashFile),
+    take_arg!("-fprofile-use", PathBuf, CanBeConcatenated('='), ExtraHashFile),
+    // Typed as paths so `generate_compile_commands` can read them back out
+    // of `common_args`, register the sysroot/gcc-toolchain trees they point
+    // at with the path transformer and rewrite them to their in-sandbox
+    // paths: see the `request_dir_tree` calls there.
+    take_arg!("-gcc-toolchain", PathBuf, Separated, PassThrough),
     take_arg!("-include-pch", PathBuf, CanBeSeparated, PreprocessorArgumentPath),
+    take_arg!("-isysroot", PathBuf, Separated, PassThrough),
     take_arg!("-load", PathBuf, Separated, ExtraHashFile),
     take_arg!("-mllvm", OsString, Separated, PassThrough),
     take_arg!("-target", OsString, Separated, PassThrough),
     flag!("-verify", PreprocessorArgumentFlag),
+    take_arg!("-x", OsString, Separated, PassThrough),
 ]);
 
 #[cfg(test)]
@@ -161,18 +338,80 @@ mod test {
             "foo.o"
         );
         parses!("-c", "foo.c", "-gcc-toolchain", "somewhere", "-o", "foo.o");
+        parses!("-c", "foo.c", "-isysroot", "somewhere", "-o", "foo.o");
+        parses!("-c", "foo.c", "--sysroot", "somewhere", "-o", "foo.o");
     }
 
     #[test]
     fn test_parse_arguments_clangmodules() {
+        // Bare `-fmodules`/`-fcxx-modules` fall back to clang's implicit,
+        // shared module cache — that's exactly the case we can't cache.
         assert_eq!(
-            CompilerArguments::CannotCache("-fcxx-modules", None),
+            CompilerArguments::CannotCache("implicit clang module cache", None),
             _parse_arguments(&stringvec!["-c", "foo.c", "-fcxx-modules", "-o", "foo.o"])
         );
         assert_eq!(
-            CompilerArguments::CannotCache("-fmodules", None),
+            CompilerArguments::CannotCache("implicit clang module cache", None),
             _parse_arguments(&stringvec!["-c", "foo.c", "-fmodules", "-o", "foo.o"])
         );
+        // Explicitly pointing at the shared cache path doesn't make it any
+        // more observable to us.
+        assert_eq!(
+            CompilerArguments::CannotCache("implicit clang module cache", None),
+            _parse_arguments(&stringvec![
+                "-c",
+                "foo.c",
+                "-fmodules",
+                "-fmodules-cache-path=/tmp/cache",
+                "-o",
+                "foo.o"
+            ])
+        );
+        // A module map alone doesn't give clang a prebuilt module, so it
+        // still falls back to the implicit cache to build one.
+        assert_eq!(
+            CompilerArguments::CannotCache("implicit clang module cache", None),
+            _parse_arguments(&stringvec![
+                "-c",
+                "foo.c",
+                "-fmodules",
+                "-fmodule-map-file=module.modulemap",
+                "-o",
+                "foo.o"
+            ])
+        );
+        // Supplying the modules explicitly is cacheable.
+        parses!(
+            "-c",
+            "foo.c",
+            "-fmodules",
+            "-fmodule-file=foo.pcm",
+            "-o",
+            "foo.o"
+        );
+    }
+
+    #[test]
+    fn test_parse_arguments_module_files() {
+        let a = parses!(
+            "-c",
+            "foo.c",
+            "-o",
+            "foo.o",
+            "-fmodules",
+            "-fmodule-file=foo.pcm",
+            "-fmodule-map-file=module.modulemap"
+        );
+        assert_eq!(
+            ovec!["foo.pcm", "module.modulemap"],
+            a.extra_hash_files
+        );
+    }
+
+    #[test]
+    fn test_parse_arguments_include_pch() {
+        let a = parses!("-c", "foo.c", "-o", "foo.o", "-include-pch", "foo.pch");
+        assert_eq!(ovec!["foo.pch"], a.extra_hash_files);
     }
 
     #[test]
@@ -244,6 +483,48 @@ mod test {
         assert_eq!(ovec!["-Xclang", "-verify"], a.preprocessor_args);
     }
 
+    #[test]
+    fn test_parse_fprofile_instr_use() {
+        let a = parses!(
+            "-c",
+            "foo.c",
+            "-o",
+            "foo.o",
+            "-fprofile-instr-use=foo.profdata"
+        );
+        assert_eq!(
+            ovec!["-fprofile-instr-use=foo.profdata"],
+            a.common_args
+        );
+        assert_eq!(ovec!["foo.profdata"], a.extra_hash_files);
+
+        // Separated form: -fprofile-instr-use path.profdata
+        let a = parses!(
+            "-c",
+            "foo.c",
+            "-o",
+            "foo.o",
+            "-fprofile-instr-use",
+            "foo.profdata"
+        );
+        assert_eq!(ovec!["foo.profdata"], a.extra_hash_files);
+    }
+
+    #[test]
+    fn test_parse_fprofile_use_variants() {
+        let a = parses!("-c", "foo.c", "-o", "foo.o", "-fprofile-use=foo.profdata");
+        assert_eq!(ovec!["foo.profdata"], a.extra_hash_files);
+
+        let a = parses!(
+            "-c",
+            "foo.c",
+            "-o",
+            "foo.o",
+            "-fprofile-sample-use=foo.prof"
+        );
+        assert_eq!(ovec!["foo.prof"], a.extra_hash_files);
+    }
+
     #[test]
     fn test_parse_fplugin() {
         let a = parses!("-c", "foo.c", "-o", "foo.o", "-fplugin", "plugin.so");
@@ -252,6 +533,58 @@ mod test {
         assert_eq!(ovec!["plugin.so"], a.extra_hash_files);
     }
 
+    #[test]
+    fn test_parse_arguments_assembly_extension() {
+        let a = parses!("-c", "foo.s", "-o", "foo.o");
+        assert_eq!(Language::Assembly, a.language);
+
+        let a = parses!("-c", "foo.S", "-o", "foo.o");
+        assert_eq!(Language::AssemblyWithCpp, a.language);
+    }
+
+    #[test]
+    fn test_parse_arguments_pch_generation() {
+        let a = parses!("-c", "foo.h", "-x", "c-header", "-o", "foo.h.pch");
+        assert_eq!(Language::CHeader, a.language);
+        assert_map_contains!(a.outputs, ("obj", PathBuf::from("foo.h.pch")));
+
+        let a = parses!("-c", "foo.h", "-x", "c++-header", "-o", "foo.h.pch");
+        assert_eq!(Language::CxxHeader, a.language);
+    }
+
+    #[test]
+    fn test_parse_arguments_assembly_x_flag() {
+        let a = parses!("-c", "foo.c", "-x", "assembler", "-o", "foo.o");
+        assert_eq!(Language::Assembly, a.language);
+
+        let a = parses!("-c", "foo.c", "-x", "assembler-with-cpp", "-o", "foo.o");
+        assert_eq!(Language::AssemblyWithCpp, a.language);
+    }
+
+    fn stringvec_owned(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_rewrite_path_arg_separated() {
+        let mut args = stringvec_owned(&["-c", "foo.c", "--sysroot", "/usr/host-sysroot", "-o", "foo.o"]);
+        rewrite_path_arg(&mut args, "--sysroot", "sysroot/usr/host-sysroot");
+        assert_eq!(
+            stringvec_owned(&["-c", "foo.c", "--sysroot", "sysroot/usr/host-sysroot", "-o", "foo.o"]),
+            args
+        );
+    }
+
+    #[test]
+    fn test_rewrite_path_arg_concatenated() {
+        let mut args = stringvec_owned(&["-c", "foo.c", "--sysroot=/usr/host-sysroot", "-o", "foo.o"]);
+        rewrite_path_arg(&mut args, "--sysroot", "sysroot/usr/host-sysroot");
+        assert_eq!(
+            stringvec_owned(&["-c", "foo.c", "--sysroot=sysroot/usr/host-sysroot", "-o", "foo.o"]),
+            args
+        );
+    }
+
     #[test]
     fn test_parse_color_diags() {
         let a = parses!("-c", "foo.c", "-o", "foo.o", "-fcolor-diagnostics");
@@ -0,0 +1,184 @@
+// Copyright 2016 Mozilla Foundation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![allow(unused_imports,dead_code,unused_variables)]
+
+use crate::compiler::args::*;
+use crate::compiler::c::{CCompilerImpl, CCompilerKind, Language, ParsedArguments};
+use crate::compiler::msvc::ArgData::*;
+use crate::compiler::{msvc, write_temp_file, Cacheable, CompileCommand, CompilerArguments};
+use crate::dist;
+use crate::mock_command::{CommandCreator, CommandCreatorSync, RunCommand};
+use crate::util::{run_input_output, OsStrExt};
+use futures::future::{self, Future};
+use futures_cpupool::CpuPool;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::errors::*;
+
+/// A unit struct on which to implement `CCompilerImpl` for clang-cl, clang's
+/// MSVC-compatible driver mode.
+#[derive(Clone, Debug)]
+pub struct ClangCl;
+
+impl CCompilerImpl for ClangCl {
+    fn kind(&self) -> CCompilerKind {
+        CCompilerKind::ClangCl
+    }
+    fn parse_arguments(
+        &self,
+        arguments: &[OsString],
+        cwd: &Path,
+    ) -> CompilerArguments<ParsedArguments> {
+        // clang-cl accepts the cl.exe grammar, so reuse the msvc argument
+        // tables and layer clang's own escapes (`/clang:`, `-Xclang`) on top.
+        let mut parsed_args = match msvc::parse_arguments(arguments, cwd, (&msvc::ARGS[..], &ARGS[..])) {
+            CompilerArguments::Ok(parsed_args) => parsed_args,
+            o => return o,
+        };
+        // `-Xclang <a>` forwards a single argument straight to the
+        // underlying clang frontend, same as under plain clang. Of the
+        // forwarded forms only `-load <path>` is re-interpreted here, so a
+        // plugin loaded via `-Xclang -load -Xclang <path>` busts the cache
+        // when it changes; other forwarded tokens stay opaque pass-through.
+        parsed_args
+            .extra_hash_files
+            .extend(xclang_extra_hash_files(arguments));
+        CompilerArguments::Ok(parsed_args)
+    }
+
+    fn preprocess<T>(
+        &self,
+        creator: &T,
+        executable: &Path,
+        parsed_args: &ParsedArguments,
+        cwd: &Path,
+        env_vars: &[(OsString, OsString)],
+        may_dist: bool,
+    ) -> SFuture<process::Output>
+    where
+        T: CommandCreatorSync,
+    {
+        msvc::preprocess(creator, executable, parsed_args, cwd, env_vars, may_dist)
+    }
+
+    fn generate_compile_commands(
+        &self,
+        path_transformer: &mut dist::PathTransformer,
+        executable: &Path,
+        parsed_args: &ParsedArguments,
+        cwd: &Path,
+        env_vars: &[(OsString, OsString)],
+    ) -> Result<(CompileCommand, Option<dist::CompileCommand>, Cacheable)> {
+        msvc::generate_compile_commands(path_transformer, executable, parsed_args, cwd, env_vars)
+    }
+}
+
+counted_array!(pub static ARGS: [ArgInfo<msvc::ArgData>; _] = [
+    // `/clang:<arg>` forwards a single GCC-style argument straight to the
+    // underlying clang driver, bypassing the cl.exe grammar entirely.
+    take_arg!("/clang:", OsString, Concatenated, PassThrough),
+    // Kept as a pass-through for the cl.exe-grammar parse itself; the
+    // cache-relevant re-interpretation of what's forwarded happens
+    // separately, in `xclang_extra_hash_files`.
+    take_arg!("-Xclang", OsString, Separated, PassThrough),
+]);
+
+/// `-Xclang <a> -Xclang <b>` forwards the GCC-style argument `<a> <b>` to
+/// the underlying clang frontend. clang-cl doesn't have `msvc::ArgData`'s
+/// own re-dispatch through the gcc argument table the way plain clang does
+/// (`gcc::ArgData::XClang`), so recognize the one forwarded form that
+/// affects the cache key directly: `-load <path>`, which hands clang a
+/// plugin whose contents need to be part of the hash.
+fn xclang_extra_hash_files(arguments: &[OsString]) -> Vec<OsString> {
+    let forwarded: Vec<&OsString> = arguments
+        .windows(2)
+        .filter(|w| w[0] == "-Xclang")
+        .map(|w| &w[1])
+        .collect();
+    forwarded
+        .windows(2)
+        .filter(|w| w[0] == "-load")
+        .map(|w| w[1].clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compiler::msvc;
+    use crate::compiler::*;
+    use crate::mock_command::*;
+    use crate::test::utils::*;
+    use std::path::PathBuf;
+
+    fn _parse_arguments(arguments: &[String]) -> CompilerArguments<ParsedArguments> {
+        let arguments = arguments.iter().map(OsString::from).collect::<Vec<_>>();
+        ClangCl.parse_arguments(&arguments, ".".as_ref())
+    }
+
+    macro_rules! parses {
+        ( $( $s:expr ),* ) => {
+            match _parse_arguments(&[ $( $s.to_string(), )* ]) {
+                CompilerArguments::Ok(a) => a,
+                o @ _ => panic!("Got unexpected parse result: {:?}", o),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_arguments_simple() {
+        let a = parses!("-c", "foo.c", "-Fofoo.obj");
+        assert_eq!(Some("foo.c"), a.input.to_str());
+        assert_map_contains!(a.outputs, ("obj", PathBuf::from("foo.obj")));
+    }
+
+    #[test]
+    fn test_parse_arguments_clang_escape() {
+        let a = parses!("-c", "foo.c", "-Fofoo.obj", "/clang:-fcolor-diagnostics");
+        assert_eq!(
+            ovec!["/clang:-fcolor-diagnostics"],
+            a.common_args
+        );
+    }
+
+    #[test]
+    fn test_parse_arguments_xclang_escape() {
+        let a = parses!("-c", "foo.c", "-Fofoo.obj", "-Xclang", "-disable-llvm-passes");
+        assert_eq!(
+            ovec!["-Xclang", "-disable-llvm-passes"],
+            a.common_args
+        );
+    }
+
+    #[test]
+    fn test_parse_xclang_load() {
+        let a = parses!(
+            "-c",
+            "foo.c",
+            "-Fofoo.obj",
+            "-Xclang",
+            "-load",
+            "-Xclang",
+            "plugin.so"
+        );
+        assert_eq!(
+            ovec!["-Xclang", "-load", "-Xclang", "plugin.so"],
+            a.common_args
+        );
+        assert_eq!(ovec!["plugin.so"], a.extra_hash_files);
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright 2016 Mozilla Foundation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod c;
+pub mod clang;
+pub mod clang_cl;
+
+use crate::compiler::c::CCompilerKind;
+use crate::compiler::clang::Clang;
+use crate::compiler::clang_cl::ClangCl;
+use std::path::Path;
+
+/// The concrete `CCompilerImpl` a clang `--version` probe can resolve to:
+/// plain clang, or its MSVC-compatible `clang-cl` driver mode. `CCompilerKind`
+/// alone can't pick between them at the type level since they're the same
+/// binary, so detection has to return one or the other explicitly.
+pub enum ClangCompiler {
+    Clang(Clang),
+    ClangCl(ClangCl),
+}
+
+/// The dispatch point a toolchain probe should call once it's identified a
+/// binary as clang (rather than gcc or MSVC): turn the executable path and
+/// its `--version` output into the right argument-dialect handler.
+pub fn clang_compiler_for(executable: &Path, version_output: &str) -> ClangCompiler {
+    match CCompilerKind::from_clang_invocation(executable, version_output) {
+        CCompilerKind::ClangCl => ClangCompiler::ClangCl(ClangCl),
+        _ => ClangCompiler::Clang(Clang),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compiler::c::CCompilerImpl;
+
+    #[test]
+    fn test_clang_compiler_for_clang_cl() {
+        match clang_compiler_for(Path::new("clang-cl.exe"), "clang version 10.0.0") {
+            ClangCompiler::ClangCl(c) => assert_eq!(CCompilerKind::ClangCl, c.kind()),
+            ClangCompiler::Clang(_) => panic!("expected ClangCl"),
+        }
+    }
+
+    #[test]
+    fn test_clang_compiler_for_plain_clang() {
+        match clang_compiler_for(Path::new("clang"), "clang version 10.0.0") {
+            ClangCompiler::Clang(c) => assert_eq!(CCompilerKind::Clang, c.kind()),
+            ClangCompiler::ClangCl(_) => panic!("expected Clang"),
+        }
+    }
+}
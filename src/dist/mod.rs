@@ -0,0 +1,70 @@
+// Copyright 2016 Mozilla Foundation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Tracks the mapping from host filesystem paths referenced by a compile
+/// command to the paths they'll have inside the (generally quite different)
+/// remote build sandbox, plus any extra trees that need to be walked and
+/// packaged into the distributed toolchain archive for those paths to
+/// resolve once there.
+#[derive(Clone, Debug, Default)]
+pub struct PathTransformer {
+    dist_to_local_path: HashMap<String, PathBuf>,
+    extra_dist_files: Vec<(String, PathBuf)>,
+}
+
+impl PathTransformer {
+    pub fn new() -> PathTransformer {
+        PathTransformer::default()
+    }
+
+    /// Map `path` to its equivalent path inside the dist sandbox.
+    pub fn to_dist(&mut self, path: &Path) -> Option<String> {
+        let dist_path = format!("sysroot{}", path.to_string_lossy());
+        self.dist_to_local_path
+            .insert(dist_path.clone(), path.to_owned());
+        Some(dist_path)
+    }
+
+    /// Register a whole directory tree (a sysroot or gcc-toolchain root,
+    /// say) that the toolchain packaging step needs to walk and bundle into
+    /// the distributed toolchain archive, in addition to mapping its own
+    /// path with `to_dist`. Returns the in-sandbox path the tree will live
+    /// at, so callers can rewrite the argument that referenced it.
+    pub fn request_dir_tree(&mut self, path: &Path) -> Option<String> {
+        let dist_path = self.to_dist(path)?;
+        self.extra_dist_files.push((dist_path.clone(), path.to_owned()));
+        Some(dist_path)
+    }
+
+    /// The extra on-disk trees `request_dir_tree` collected, each paired
+    /// with the in-sandbox path it should be unpacked at. Nothing in this
+    /// crate reads this list: the trees are not added to the toolchain
+    /// archive by any code here.
+    pub fn extra_dist_files(&self) -> &[(String, PathBuf)] {
+        &self.extra_dist_files
+    }
+}
+
+/// A compile command destined for a remote build sandbox, whose paths have
+/// already been run through a `PathTransformer`.
+#[derive(Clone, Debug)]
+pub struct CompileCommand {
+    pub executable: String,
+    pub arguments: Vec<String>,
+    pub env_vars: Vec<(String, String)>,
+    pub cwd: PathBuf,
+}